@@ -0,0 +1,166 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use proxydis::cache::{Cache, CacheOption};
+use sha2::Sha256;
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs()
+}
+
+/// Builds a verification-cache key that can't be reinterpreted with a different
+/// `resource`/`token` split: `resource` is length-prefixed, so a `resource` containing
+/// a `:` can't shift the boundary and get treated as a cache hit for a different,
+/// never-verified pair.
+fn cache_key(resource: &str, token: &str) -> String {
+    format!("{}:{resource}:{token}", resource.len())
+}
+
+/// Verifies `resource:expires_at:signature` bearer tokens against a configured secret
+/// and caches successful verifications for `verification_cache_ttl` milliseconds, storing
+/// the token's own `expires_at` with each entry, so a repeated request for the same token
+/// skips re-hashing it. A cache hit
+/// still re-checks `expires_at` against the current time, so a token that expires while
+/// its cache entry is still alive is rejected rather than waved through.
+pub struct Authorizer {
+    secret: Vec<u8>,
+    verified: Cache<u64>,
+}
+
+impl Authorizer {
+    pub fn new(secret: impl Into<Vec<u8>>, verification_cache_ttl: u128) -> Self {
+        Self {
+            secret: secret.into(),
+            verified: Cache::new(verification_cache_ttl),
+        }
+    }
+
+    /// Returns `true` when `token` is a currently-valid, unexpired token for `resource`.
+    pub fn verify(&self, token: &str, resource: &str) -> bool {
+        let cache_key = cache_key(resource, token);
+
+        if let CacheOption::Value(expires_at) = self.verified.get(&cache_key) {
+            return now_secs() < expires_at;
+        }
+
+        let expires_at = match self.verify_signature(token, resource) {
+            Some(expires_at) => expires_at,
+            None => return false,
+        };
+
+        self.verified.create(cache_key.clone());
+        self.verified.update(&cache_key, expires_at);
+        true
+    }
+
+    fn verify_signature(&self, token: &str, resource: &str) -> Option<u64> {
+        let mut parts = token.splitn(3, ':');
+        let token_resource = parts.next()?;
+        let expires_at = parts.next().and_then(|value| value.parse::<u64>().ok())?;
+        let signature = parts.next()?;
+
+        if token_resource != resource {
+            return None;
+        }
+
+        if expires_at < now_secs() {
+            return None;
+        }
+
+        if !constant_time_eq(&self.sign(token_resource, expires_at), signature) {
+            return None;
+        }
+
+        Some(expires_at)
+    }
+
+    fn sign(&self, resource: &str, expires_at: u64) -> String {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts a key of any length");
+        mac.update(format!("{resource}:{expires_at}").as_bytes());
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issue_token(authorizer: &Authorizer, resource: &str, expires_at: u64) -> String {
+        format!("{resource}:{expires_at}:{}", authorizer.sign(resource, expires_at))
+    }
+
+    #[test]
+    fn accepts_a_valid_unexpired_token() {
+        let authorizer = Authorizer::new("secret", 1000);
+        let token = issue_token(&authorizer, "/videos/1", u64::MAX);
+
+        assert!(authorizer.verify(&token, "/videos/1"));
+    }
+
+    #[test]
+    fn rejects_an_expired_token() {
+        let authorizer = Authorizer::new("secret", 1000);
+        let token = issue_token(&authorizer, "/videos/1", 0);
+
+        assert!(!authorizer.verify(&token, "/videos/1"));
+    }
+
+    #[test]
+    fn rejects_a_token_bound_to_a_different_resource() {
+        let authorizer = Authorizer::new("secret", 1000);
+        let token = issue_token(&authorizer, "/videos/1", u64::MAX);
+
+        assert!(!authorizer.verify(&token, "/videos/2"));
+    }
+
+    #[test]
+    fn rejects_a_token_signed_with_a_different_secret() {
+        let issuer = Authorizer::new("secret", 1000);
+        let verifier = Authorizer::new("other-secret", 1000);
+        let token = issue_token(&issuer, "/videos/1", u64::MAX);
+
+        assert!(!verifier.verify(&token, "/videos/1"));
+    }
+
+    #[test]
+    fn rejects_a_cached_token_once_it_expires_even_with_a_long_verification_cache_ttl() {
+        use std::{thread::sleep, time::Duration};
+
+        let authorizer = Authorizer::new("secret", 60_000);
+        let expires_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards")
+            .as_secs()
+            + 1;
+        let token = issue_token(&authorizer, "/videos/1", expires_at);
+
+        assert!(authorizer.verify(&token, "/videos/1"));
+
+        sleep(Duration::from_millis(1_500));
+
+        assert!(!authorizer.verify(&token, "/videos/1"));
+    }
+
+    #[test]
+    fn cache_key_does_not_collide_across_a_shifted_resource_token_boundary() {
+        // "/a" + ":" + "b:token" and "/a:b" + ":" + "token" join to the same raw string;
+        // length-prefixing the resource must keep their cache keys distinct.
+        assert_ne!(cache_key("/a", "b:token"), cache_key("/a:b", "token"));
+    }
+}
@@ -1,36 +1,167 @@
 use async_trait::async_trait;
-use hyper::{
-    server::conn::AddrStream,
-    service::{make_service_fn, service_fn},
-    Body, Request, Response, Server,
-};
+use hyper::{header::AUTHORIZATION, server::conn::Http, service::service_fn, Body, Request, Response, StatusCode};
 use std::{
     convert::Infallible,
-    future::Future,
     net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
+use tokio::{net::TcpListener, sync::Semaphore};
+
+use crate::auth::Authorizer;
 
 #[async_trait]
 trait Service {
     async fn init(&self, request: Request<Body>, ip: IpAddr) -> Result<Response<Body>, Infallible>;
 }
 
-pub async fn server<S>(port: u16, handler: S)
+/// Connection-lifetime knobs for `server`. `keep_alive_timeout` bounds how long an idle
+/// keep-alive connection is held open, `request_read_timeout` bounds how long the server
+/// waits to finish reading a client's request headers, and `client_disconnect_timeout`
+/// bounds how long a connection is kept open after that to let the client close it.
+/// `max_connections`, when set, caps concurrent connections so slow or idle clients
+/// can't exhaust server resources. `authorizer`, when set, gates every request behind a
+/// bearer token before it reaches the handler.
+pub struct ServerConfig {
+    pub bind_address: IpAddr,
+    pub port: u16,
+    pub keep_alive_timeout: Duration,
+    pub request_read_timeout: Duration,
+    pub client_disconnect_timeout: Duration,
+    pub max_connections: Option<usize>,
+    pub authorizer: Option<Arc<Authorizer>>,
+}
+
+impl ServerConfig {
+    pub fn new(port: u16) -> Self {
+        Self {
+            bind_address: IpAddr::from([0, 0, 0, 0]),
+            port,
+            keep_alive_timeout: Duration::from_secs(75),
+            request_read_timeout: Duration::from_secs(30),
+            client_disconnect_timeout: Duration::from_secs(10),
+            max_connections: None,
+            authorizer: None,
+        }
+    }
+}
+
+fn forbidden() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .body(Body::empty())
+        .expect("a static response always builds")
+}
+
+pub async fn server<S>(config: ServerConfig, handler: S)
 where
-    S: Service + Sync,
+    S: Service + Sync + Send + 'static,
 {
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-
-    let service = make_service_fn(move |conn: &AddrStream| {
-        let ip = conn.remote_addr().ip();
-        async move {
-            Ok::<_, Infallible>(service_fn(move |req| handler.init(req, ip.clone())));
+    let addr = SocketAddr::new(config.bind_address, config.port);
+    let listener = match TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::error!("Failed to bind {addr}: {err}");
+            return;
         }
-    });
+    };
 
-    let server = Server::bind(&addr).serve(service);
+    let handler = Arc::new(handler);
+    let connection_limit = config.max_connections.map(|max| Arc::new(Semaphore::new(max)));
+    let idle_timeout = config.keep_alive_timeout + config.client_disconnect_timeout;
 
     log::info!("Running on: {}", &addr.to_string());
 
-    server.await.unwrap_or(());
+    loop {
+        let (stream, remote_addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                log::warn!("Failed to accept connection: {err}");
+                continue;
+            }
+        };
+
+        let permit = match &connection_limit {
+            Some(semaphore) => match semaphore.clone().try_acquire_owned() {
+                Ok(permit) => Some(permit),
+                Err(_) => {
+                    log::warn!("Connection limit reached, dropping connection from {remote_addr}");
+                    continue;
+                }
+            },
+            None => None,
+        };
+
+        let handler = handler.clone();
+        let ip = remote_addr.ip();
+        let request_read_timeout = config.request_read_timeout;
+        let authorizer = config.authorizer.clone();
+        let last_activity = Arc::new(Mutex::new(Instant::now()));
+
+        tokio::spawn(async move {
+            let _permit = permit;
+
+            let last_activity_for_service = last_activity.clone();
+            let service = service_fn(move |req| {
+                let handler = handler.clone();
+                let authorizer = authorizer.clone();
+                let last_activity = last_activity_for_service.clone();
+                async move {
+                    *last_activity.lock().unwrap() = Instant::now();
+
+                    if let Some(authorizer) = &authorizer {
+                        let resource = req.uri().path().to_string();
+                        let token = req
+                            .headers()
+                            .get(AUTHORIZATION)
+                            .and_then(|value| value.to_str().ok())
+                            .and_then(|value| value.strip_prefix("Bearer "));
+
+                        let authorized = match token {
+                            Some(token) => authorizer.verify(token, &resource),
+                            None => false,
+                        };
+                        if !authorized {
+                            return Ok(forbidden());
+                        }
+                    }
+
+                    let response = handler.init(req, ip).await;
+                    *last_activity.lock().unwrap() = Instant::now();
+                    response
+                }
+            });
+
+            let connection = Http::new()
+                .http1_keep_alive(true)
+                .http1_header_read_timeout(request_read_timeout)
+                .serve_connection(stream, service)
+                .with_upgrades();
+            tokio::pin!(connection);
+
+            // Races the connection against a timer that resets every time a request
+            // starts or finishes, so a client that keeps the connection busy is never
+            // killed mid-stream; only a connection that's genuinely gone idle for
+            // longer than `idle_timeout` gets dropped.
+            let idle_watchdog = async {
+                loop {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    if last_activity.lock().unwrap().elapsed() >= idle_timeout {
+                        return;
+                    }
+                }
+            };
+
+            tokio::select! {
+                result = &mut connection => {
+                    if let Err(err) = result {
+                        log::warn!("Connection from {remote_addr} ended with an error: {err}");
+                    }
+                }
+                _ = idle_watchdog => {
+                    log::warn!("Connection from {remote_addr} idle for longer than {idle_timeout:?}, closing");
+                }
+            }
+        });
+    }
 }
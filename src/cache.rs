@@ -1,9 +1,19 @@
 use std::{
-    collections::HashMap,
-    fmt::Debug,
+    collections::{HashMap, VecDeque},
+    fmt::{self, Debug},
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
     time::{SystemTime, UNIX_EPOCH},
 };
 
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::sync::Notify;
+
+use crate::storage::Storage;
+
 fn time_now() -> u128 {
     let start = SystemTime::now();
     let since_the_epoch = start
@@ -66,8 +76,8 @@ impl<T> CacheOption<T> {
         }
     }
 }
-#[derive(Default, Debug, PartialEq)]
-struct CacheValue<T> {
+#[derive(Default, Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub(crate) struct CacheValue<T> {
     pub value: T,
     ttl_timestamp: u128,
 }
@@ -81,10 +91,56 @@ impl<T> CacheValue<T> {
     }
 }
 
-#[derive(Default, Debug)]
-struct Cache<T> {
-    pub items: HashMap<String, Option<CacheValue<T>>>,
+impl<T> CacheValue<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("cache value should serialize")
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        serde_json::from_slice(bytes).ok()
+    }
+}
+
+#[derive(Default)]
+pub struct Cache<T> {
+    items: Mutex<HashMap<String, Option<CacheValue<T>>>>,
+    order: Mutex<VecDeque<String>>,
+    in_flight: Mutex<HashMap<String, Arc<Notify>>>,
     ttl: u128,
+    capacity: Option<usize>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    min_miss_interval: u128,
+    negative_ttl: Option<u128>,
+    last_miss: Mutex<HashMap<String, u128>>,
+    negative: Mutex<HashMap<String, u128>>,
+}
+
+/// The outcome of a rate-limited lookup: a fresh hit, a confirmed miss the caller may
+/// act on, or a signal that a miss was already attempted too recently and the caller
+/// should serve something stale (or wait) instead of hitting the origin again.
+#[derive(Debug, PartialEq)]
+pub enum Answer<T> {
+    Found(T),
+    NotFound,
+    RateLimited,
+}
+
+impl<T: Debug> Debug for Cache<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cache")
+            .field("items", &self.items)
+            .field("ttl", &self.ttl)
+            .finish()
+    }
+}
+
+enum FetchSlot {
+    Leader(Arc<Notify>),
+    Follower(Arc<Notify>),
 }
 
 impl<T> Cache<T>
@@ -93,48 +149,315 @@ where
 {
     pub fn new(ttl: u128) -> Self {
         Self {
-            items: HashMap::default(),
+            items: Mutex::new(HashMap::default()),
+            order: Mutex::new(VecDeque::default()),
+            in_flight: Mutex::new(HashMap::default()),
             ttl,
+            capacity: None,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            min_miss_interval: 0,
+            negative_ttl: None,
+            last_miss: Mutex::new(HashMap::default()),
+            negative: Mutex::new(HashMap::default()),
         }
     }
 
-    pub fn create(&mut self, key: String) {
-        self.items.insert(key, None);
+    pub fn with_lifespan_and_capacity(ttl: u128, capacity: usize) -> Self {
+        Self {
+            capacity: Some(capacity),
+            ..Self::new(ttl)
+        }
     }
 
-    pub fn update(&mut self, key: &String, value: T) {
-        if let Some(item) = self.items.get_mut(key) {
+    /// Enables miss rate-limiting: a miss on `key` within `min_miss_interval` of the
+    /// previous one returns `Answer::RateLimited` instead of `Answer::NotFound`. When
+    /// `negative_ttl` is set, `mark_not_found` can pin a key as absent for that long.
+    pub fn with_rate_limited_misses(mut self, min_miss_interval: u128, negative_ttl: Option<u128>) -> Self {
+        self.min_miss_interval = min_miss_interval;
+        self.negative_ttl = negative_ttl;
+        self
+    }
+
+    pub fn create(&self, key: String) {
+        self.touch_order(&key);
+        self.items.lock().unwrap().insert(key, None);
+        self.evict_if_needed();
+    }
+
+    pub fn update(&self, key: &String, value: T) {
+        self.touch_order(key);
+        let mut items = self.items.lock().unwrap();
+        if let Some(item) = items.get_mut(key) {
             *item = Some(CacheValue::new(value, self.ttl));
         } else {
-            self.create(key.clone());
+            items.insert(key.clone(), Some(CacheValue::new(value, self.ttl)));
         }
+        drop(items);
+
+        // A fresh value means `key` is no longer known-absent or rate-limited.
+        self.negative.lock().unwrap().remove(key);
+        self.last_miss.lock().unwrap().remove(key);
+
+        self.evict_if_needed();
     }
 
-    pub fn get(self, key: &String) -> CacheOption<T> {
-        match self.items.get(key) {
+    pub fn get(&self, key: &String) -> CacheOption<T> {
+        match self.items.lock().unwrap().get(key) {
             Some(value) => match value {
                 Some(cache_value) => {
                     if cache_value.ttl_timestamp >= time_now() {
+                        self.touch_order(key);
+                        self.hits.fetch_add(1, Ordering::Relaxed);
                         CacheOption::Value(cache_value.value.clone())
                     } else {
+                        self.misses.fetch_add(1, Ordering::Relaxed);
                         CacheOption::Expired
                     }
                 }
-                None => CacheOption::Empty,
+                None => {
+                    self.misses.fetch_add(1, Ordering::Relaxed);
+                    CacheOption::Empty
+                }
             },
-            None => CacheOption::Undefined,
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                CacheOption::Undefined
+            }
         }
     }
 
-    pub fn remove(&mut self, key: &String) -> Option<Option<CacheValue<T>>> {
-        self.items.remove(key)
+    pub fn remove(&self, key: &String) -> Option<Option<CacheValue<T>>> {
+        let mut order = self.order.lock().unwrap();
+        if let Some(pos) = order.iter().position(|k| k == key) {
+            order.remove(pos);
+        }
+        drop(order);
+        self.items.lock().unwrap().remove(key)
     }
 
-    pub fn clean(&mut self, key: &String) {
-        if let Some(item) = self.items.get_mut(key) {
+    pub fn clean(&self, key: &String) {
+        if let Some(item) = self.items.lock().unwrap().get_mut(key) {
             *item = None;
         }
     }
+
+    /// Drops every entry whose TTL has already elapsed. Entries only transition to
+    /// `Expired` lazily when read through `get`, so a background sweep is needed to
+    /// reclaim keys that are never looked up again. Also prunes the negative-cache and
+    /// miss-rate-limiting tables, which would otherwise grow without bound. Returns the
+    /// number of entries removed.
+    pub(crate) fn sweep_expired(&self) -> usize {
+        let now = time_now();
+        let mut items = self.items.lock().unwrap();
+        let expired_keys: Vec<String> = items
+            .iter()
+            .filter_map(|(key, value)| match value {
+                Some(cache_value) if cache_value.ttl_timestamp < now => Some(key.clone()),
+                _ => None,
+            })
+            .collect();
+
+        for key in &expired_keys {
+            items.remove(key);
+        }
+        drop(items);
+
+        let mut order = self.order.lock().unwrap();
+        order.retain(|key| !expired_keys.contains(key));
+        drop(order);
+
+        self.negative.lock().unwrap().retain(|_, expiry| *expiry >= now);
+
+        if self.min_miss_interval > 0 {
+            let min_miss_interval = self.min_miss_interval;
+            self.last_miss
+                .lock()
+                .unwrap()
+                .retain(|_, last| now.saturating_sub(*last) < min_miss_interval);
+        }
+
+        expired_keys.len()
+    }
+
+    pub fn ttl(&self) -> u128 {
+        self.ttl
+    }
+
+    /// Pins `key` as a known-absent negative cache entry for `negative_ttl`, so
+    /// `lookup` short-circuits to `Answer::RateLimited` instead of letting a caller
+    /// re-hammer an upstream that just answered 404 for it.
+    pub fn mark_not_found(&self, key: &String) {
+        if let Some(negative_ttl) = self.negative_ttl {
+            self.negative
+                .lock()
+                .unwrap()
+                .insert(key.clone(), time_now() + negative_ttl);
+        }
+    }
+
+    /// Rate-limited variant of `get`: a fresh hit is `Answer::Found`, a miss is
+    /// `Answer::NotFound`, and a miss that arrives within `min_miss_interval` of the
+    /// previous one (or while `key` is pinned via `mark_not_found`) is
+    /// `Answer::RateLimited`.
+    pub fn lookup(&self, key: &String) -> Answer<T> {
+        if let Some(expiry) = self.negative.lock().unwrap().get(key).copied() {
+            if expiry >= time_now() {
+                return Answer::RateLimited;
+            }
+        }
+
+        match self.get(key) {
+            CacheOption::Value(value) => Answer::Found(value),
+            _ => {
+                if self.min_miss_interval == 0 {
+                    return Answer::NotFound;
+                }
+
+                let now = time_now();
+                let mut last_miss = self.last_miss.lock().unwrap();
+                let rate_limited = last_miss
+                    .get(key)
+                    .map(|last| now.saturating_sub(*last) < self.min_miss_interval)
+                    .unwrap_or(false);
+
+                if rate_limited {
+                    return Answer::RateLimited;
+                }
+
+                last_miss.insert(key.clone(), now);
+                Answer::NotFound
+            }
+        }
+    }
+
+    /// Inserts `value` under `key` with an explicit TTL instead of `self.ttl`, for
+    /// callers (such as gossip) replaying a remaining TTL carried over the network.
+    pub(crate) fn insert_with_ttl(&self, key: &String, value: T, ttl: u128) {
+        self.touch_order(key);
+        self.items
+            .lock()
+            .unwrap()
+            .insert(key.clone(), Some(CacheValue::new(value, ttl)));
+        self.evict_if_needed();
+    }
+
+    pub fn cache_hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn cache_misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    fn touch_order(&self, key: &String) {
+        let mut order = self.order.lock().unwrap();
+        if let Some(pos) = order.iter().position(|k| k == key) {
+            order.remove(pos);
+        }
+        order.push_back(key.clone());
+    }
+
+    fn evict_if_needed(&self) {
+        let capacity = match self.capacity {
+            Some(capacity) => capacity,
+            None => return,
+        };
+
+        let mut items = self.items.lock().unwrap();
+        let mut order = self.order.lock().unwrap();
+
+        while items.len() > capacity {
+            match order.pop_front() {
+                Some(lru_key) => {
+                    items.remove(&lru_key);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+impl<T> Cache<T>
+where
+    T: Default + Debug + PartialEq + Clone + Serialize + DeserializeOwned,
+{
+    /// Writes the in-memory entry for `key` through to `storage` so it survives a restart.
+    pub async fn persist<S: Storage>(&self, key: &String, storage: &S) {
+        let snapshot = self.items.lock().unwrap().get(key).cloned().flatten();
+
+        if let Some(cache_value) = snapshot {
+            storage.set(key, cache_value.to_bytes()).await;
+        }
+    }
+
+    /// Loads `key` back from `storage` into memory, preserving its original TTL.
+    pub async fn restore<S: Storage>(&self, key: &String, storage: &S) {
+        let bytes = match storage.get(key).await {
+            Some(bytes) => bytes,
+            None => return,
+        };
+
+        if let Some(cache_value) = CacheValue::<T>::from_bytes(&bytes) {
+            self.touch_order(key);
+            self.items
+                .lock()
+                .unwrap()
+                .insert(key.clone(), Some(cache_value));
+            self.evict_if_needed();
+        }
+    }
+}
+
+impl<T> Cache<T>
+where
+    T: Default + Debug + PartialEq + Clone,
+{
+    /// Cache-aside lookup: returns the cached value on a fresh hit, otherwise awaits
+    /// `compute` and stores its result under a new TTL. Concurrent misses on the same
+    /// key share one in-flight `compute` call instead of each hitting the origin.
+    pub async fn get_or_fetch<F, Fut, E>(&self, key: &String, compute: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        loop {
+            if let CacheOption::Value(value) = self.get(key) {
+                return Ok(value);
+            }
+
+            let slot = {
+                let mut in_flight = self.in_flight.lock().unwrap();
+                match in_flight.get(key) {
+                    Some(notify) => FetchSlot::Follower(notify.clone()),
+                    None => {
+                        let notify = Arc::new(Notify::new());
+                        in_flight.insert(key.clone(), notify.clone());
+                        FetchSlot::Leader(notify)
+                    }
+                }
+            };
+
+            match slot {
+                FetchSlot::Follower(notify) => {
+                    notify.notified().await;
+                    continue;
+                }
+                FetchSlot::Leader(notify) => {
+                    let result = compute().await;
+                    self.in_flight.lock().unwrap().remove(key);
+
+                    let result = result.map(|value| {
+                        self.update(key, value.clone());
+                        value
+                    });
+                    notify.notify_waiters();
+
+                    return result;
+                }
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -142,7 +465,7 @@ pub mod tests {
     use super::*;
     use std::{thread::sleep, time::Duration};
 
-    #[derive(Default, Debug, PartialEq, Clone)]
+    #[derive(Default, Debug, PartialEq, Clone, Serialize, Deserialize)]
     struct DataTest {
         value: String,
     }
@@ -156,7 +479,7 @@ pub mod tests {
 
     #[test]
     fn value_empty() {
-        let mut cache = Cache::<DataTest>::new(1000);
+        let cache = Cache::<DataTest>::new(1000);
         let key = "key".to_string();
         cache.create(key.clone());
 
@@ -165,7 +488,7 @@ pub mod tests {
 
     #[test]
     fn value_found() {
-        let mut cache = Cache::<DataTest>::new(1000);
+        let cache = Cache::<DataTest>::new(1000);
         let key = "key".to_string();
         let value = DataTest::default();
         cache.create(key.clone());
@@ -176,7 +499,7 @@ pub mod tests {
 
     #[test]
     fn value_match() {
-        let mut cache = Cache::<DataTest>::new(1000);
+        let cache = Cache::<DataTest>::new(1000);
         let key = "key".to_string();
         let value = DataTest::default();
         cache.create(key.clone());
@@ -187,7 +510,7 @@ pub mod tests {
 
     #[test]
     fn value_expired() {
-        let mut cache = Cache::<DataTest>::new(0);
+        let cache = Cache::<DataTest>::new(0);
         let key = "key".to_string();
         let value = DataTest::default();
         cache.create(key.clone());
@@ -199,7 +522,7 @@ pub mod tests {
 
     #[test]
     fn value_unwrap_or() {
-        let mut cache = Cache::<DataTest>::new(5);
+        let cache = Cache::<DataTest>::new(5);
         let key = "key".to_string();
         let value = DataTest::default();
         cache.create(key.clone());
@@ -209,7 +532,7 @@ pub mod tests {
 
     #[test]
     fn value_remove() {
-        let mut cache = Cache::<DataTest>::new(1000);
+        let cache = Cache::<DataTest>::new(1000);
         let key = "key".to_string();
         let value = DataTest::default();
         cache.create(key.clone());
@@ -221,7 +544,7 @@ pub mod tests {
 
     #[test]
     fn value_clean() {
-        let mut cache = Cache::<DataTest>::new(1000);
+        let cache = Cache::<DataTest>::new(1000);
         let key = "key".to_string();
         let value = DataTest::default();
         cache.create(key.clone());
@@ -230,4 +553,172 @@ pub mod tests {
 
         assert_eq!(cache.get(&key).is_empty(), true);
     }
+
+    #[test]
+    fn capacity_evicts_least_recently_used() {
+        let cache = Cache::<DataTest>::with_lifespan_and_capacity(1000, 2);
+        let value = DataTest::default();
+
+        cache.create("a".to_string());
+        cache.update(&"a".to_string(), value.clone());
+        cache.create("b".to_string());
+        cache.update(&"b".to_string(), value.clone());
+        cache.get(&"a".to_string());
+        cache.create("c".to_string());
+        cache.update(&"c".to_string(), value.clone());
+
+        assert_eq!(cache.get(&"b".to_string()).is_undefined(), true);
+        assert_eq!(cache.get(&"a".to_string()).is_value(), true);
+        assert_eq!(cache.get(&"c".to_string()).is_value(), true);
+    }
+
+    #[test]
+    fn hit_and_miss_counters() {
+        let cache = Cache::<DataTest>::new(1000);
+        let key = "key".to_string();
+        let value = DataTest::default();
+
+        cache.get(&key);
+        cache.create(key.clone());
+        cache.update(&key, value);
+        cache.get(&key);
+
+        assert_eq!(cache.cache_misses(), 1);
+        assert_eq!(cache.cache_hits(), 1);
+    }
+
+    #[test]
+    fn sweep_expired_removes_stale_entries() {
+        let cache = Cache::<DataTest>::new(0);
+        let key = "key".to_string();
+        let value = DataTest::default();
+        cache.create(key.clone());
+        cache.update(&key, value.clone());
+        sleep(Duration::from_millis(1));
+
+        assert_eq!(cache.sweep_expired(), 1);
+        assert_eq!(cache.get(&key).is_undefined(), true);
+    }
+
+    #[test]
+    fn lookup_rate_limits_repeated_misses() {
+        let cache = Cache::<DataTest>::new(1000).with_rate_limited_misses(1000, None);
+        let key = "key".to_string();
+
+        assert_eq!(cache.lookup(&key), Answer::NotFound);
+        assert_eq!(cache.lookup(&key), Answer::RateLimited);
+    }
+
+    #[test]
+    fn mark_not_found_pins_a_negative_answer() {
+        let cache = Cache::<DataTest>::new(1000).with_rate_limited_misses(0, Some(1000));
+        let key = "key".to_string();
+
+        assert_eq!(cache.lookup(&key), Answer::NotFound);
+        cache.mark_not_found(&key);
+
+        assert_eq!(cache.lookup(&key), Answer::RateLimited);
+    }
+
+    #[test]
+    fn lookup_returns_found_for_a_fresh_value() {
+        let cache = Cache::<DataTest>::new(1000).with_rate_limited_misses(1000, Some(1000));
+        let key = "key".to_string();
+        let value = DataTest::default();
+        cache.create(key.clone());
+        cache.update(&key, value.clone());
+
+        assert_eq!(cache.lookup(&key), Answer::Found(value));
+    }
+
+    #[test]
+    fn update_clears_a_negative_pin() {
+        let cache = Cache::<DataTest>::new(1000).with_rate_limited_misses(0, Some(1000));
+        let key = "key".to_string();
+        cache.mark_not_found(&key);
+        assert_eq!(cache.lookup(&key), Answer::RateLimited);
+
+        let value = DataTest::default();
+        cache.create(key.clone());
+        cache.update(&key, value.clone());
+
+        assert_eq!(cache.lookup(&key), Answer::Found(value));
+    }
+
+    #[test]
+    fn sweep_expired_prunes_negative_and_last_miss_tables() {
+        let cache = Cache::<DataTest>::new(1000).with_rate_limited_misses(1, Some(1));
+        let key = "key".to_string();
+
+        let other_key = "other".to_string();
+
+        cache.mark_not_found(&key);
+        assert_eq!(cache.lookup(&key), Answer::RateLimited);
+        assert_eq!(cache.lookup(&other_key), Answer::NotFound);
+        sleep(Duration::from_millis(5));
+
+        cache.sweep_expired();
+
+        // Both tables were pruned, so the key behaves as never having missed before.
+        assert!(cache.negative.lock().unwrap().is_empty());
+        assert!(cache.last_miss.lock().unwrap().is_empty());
+        assert_eq!(cache.lookup(&key), Answer::NotFound);
+    }
+
+    #[tokio::test]
+    async fn persist_and_restore_round_trip_through_storage() {
+        use crate::storage::FileStorage;
+
+        let dir = std::env::temp_dir().join(format!("proxydis-cache-test-{:x}", std::process::id()));
+        let storage = FileStorage::new(dir.clone());
+        let key = "key".to_string();
+        let value = DataTest {
+            value: "durable".to_string(),
+        };
+
+        let cache = Cache::<DataTest>::new(1000);
+        cache.create(key.clone());
+        cache.update(&key, value.clone());
+        cache.persist(&key, &storage).await;
+
+        let restored = Cache::<DataTest>::new(1000);
+        restored.restore(&key, &storage).await;
+
+        assert_eq!(restored.get(&key).unwrap(), value);
+
+        tokio::fs::remove_dir_all(dir).await.ok();
+    }
+
+    #[tokio::test]
+    async fn get_or_fetch_computes_on_miss() {
+        let cache = Cache::<DataTest>::new(1000);
+        let key = "key".to_string();
+        let value = DataTest {
+            value: "computed".to_string(),
+        };
+
+        let result = cache
+            .get_or_fetch(&key, || async { Ok::<_, ()>(value.clone()) })
+            .await;
+
+        assert_eq!(result, Ok(value.clone()));
+        assert_eq!(cache.get(&key).unwrap(), value);
+    }
+
+    #[tokio::test]
+    async fn get_or_fetch_returns_cached_value_without_recomputing() {
+        let cache = Cache::<DataTest>::new(1000);
+        let key = "key".to_string();
+        let value = DataTest {
+            value: "cached".to_string(),
+        };
+        cache.create(key.clone());
+        cache.update(&key, value.clone());
+
+        let result: Result<DataTest, ()> = cache
+            .get_or_fetch(&key, || async { panic!("should not recompute a fresh hit") })
+            .await;
+
+        assert_eq!(result, Ok(value));
+    }
 }
@@ -0,0 +1,267 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::net::UdpSocket;
+
+use crate::cache::Cache;
+
+fn now_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis()
+}
+
+const OP_INVALIDATE: u8 = 0;
+const OP_STORE: u8 = 1;
+
+enum Payload {
+    Invalidate,
+    Store { value: Vec<u8>, remaining_ttl: u128 },
+}
+
+struct Message {
+    node_id: u64,
+    timestamp: u128,
+    key: String,
+    payload: Payload,
+}
+
+impl Message {
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.push(match self.payload {
+            Payload::Invalidate => OP_INVALIDATE,
+            Payload::Store { .. } => OP_STORE,
+        });
+        bytes.extend_from_slice(&self.node_id.to_le_bytes());
+        bytes.extend_from_slice(&self.timestamp.to_le_bytes());
+
+        let key_bytes = self.key.as_bytes();
+        bytes.extend_from_slice(&(key_bytes.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(key_bytes);
+
+        if let Payload::Store { value, remaining_ttl } = &self.payload {
+            bytes.extend_from_slice(&remaining_ttl.to_le_bytes());
+            bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(value);
+        }
+
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let op = *bytes.first()?;
+        let mut cursor = 1;
+
+        let node_id = u64::from_le_bytes(bytes.get(cursor..cursor + 8)?.try_into().ok()?);
+        cursor += 8;
+
+        let timestamp = u128::from_le_bytes(bytes.get(cursor..cursor + 16)?.try_into().ok()?);
+        cursor += 16;
+
+        let key_len = u32::from_le_bytes(bytes.get(cursor..cursor + 4)?.try_into().ok()?) as usize;
+        cursor += 4;
+
+        let key = String::from_utf8(bytes.get(cursor..cursor + key_len)?.to_vec()).ok()?;
+        cursor += key_len;
+
+        let payload = match op {
+            OP_INVALIDATE => Payload::Invalidate,
+            OP_STORE => {
+                let remaining_ttl = u128::from_le_bytes(bytes.get(cursor..cursor + 16)?.try_into().ok()?);
+                cursor += 16;
+
+                let value_len = u32::from_le_bytes(bytes.get(cursor..cursor + 4)?.try_into().ok()?) as usize;
+                cursor += 4;
+
+                let value = bytes.get(cursor..cursor + value_len)?.to_vec();
+                Payload::Store { value, remaining_ttl }
+            }
+            _ => return None,
+        };
+
+        Some(Self {
+            node_id,
+            timestamp,
+            key,
+            payload,
+        })
+    }
+}
+
+/// Keeps a local `Cache<T>` loosely coherent with peers over UDP: a local `update`,
+/// `remove`, or `clean` is broadcast to every peer, and inbound messages are applied to
+/// the local cache. Each message carries a node id and timestamp so a node ignores
+/// echoes of its own writes and updates older than the last one it already applied,
+/// which prevents invalidation loops.
+pub struct Gossip<T> {
+    socket: UdpSocket,
+    peers: Vec<SocketAddr>,
+    node_id: u64,
+    cache: Arc<Cache<T>>,
+    seen: Mutex<HashMap<String, (u64, u128)>>,
+}
+
+impl<T> Gossip<T>
+where
+    T: Default + Debug + PartialEq + Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    pub async fn bind(
+        bind_addr: SocketAddr,
+        peers: Vec<SocketAddr>,
+        node_id: u64,
+        cache: Arc<Cache<T>>,
+    ) -> io::Result<Arc<Self>> {
+        let socket = UdpSocket::bind(bind_addr).await?;
+        let gossip = Arc::new(Self {
+            socket,
+            peers,
+            node_id,
+            cache,
+            seen: Mutex::new(HashMap::new()),
+        });
+
+        tokio::spawn(gossip.clone().listen());
+
+        Ok(gossip)
+    }
+
+    pub async fn update(&self, key: &String, value: T) {
+        self.cache.update(key, value.clone());
+        let value = serde_json::to_vec(&value).unwrap_or_default();
+        self.publish(key, Payload::Store {
+            value,
+            remaining_ttl: self.cache.ttl(),
+        })
+        .await;
+    }
+
+    pub async fn remove(&self, key: &String) {
+        self.cache.remove(key);
+        self.publish(key, Payload::Invalidate).await;
+    }
+
+    pub async fn clean(&self, key: &String) {
+        self.cache.clean(key);
+        self.publish(key, Payload::Invalidate).await;
+    }
+
+    async fn publish(&self, key: &String, payload: Payload) {
+        let message = Message {
+            node_id: self.node_id,
+            timestamp: now_millis(),
+            key: key.clone(),
+            payload,
+        };
+        let bytes = message.encode();
+
+        for peer in &self.peers {
+            self.socket.send_to(&bytes, peer).await.ok();
+        }
+    }
+
+    async fn listen(self: Arc<Self>) {
+        let mut buf = [0u8; 65_536];
+        loop {
+            let (len, addr) = match self.socket.recv_from(&mut buf).await {
+                Ok(received) => received,
+                Err(_) => continue,
+            };
+
+            // Only configured peers may mutate this node's cache; anything else reaching
+            // the socket is dropped before it's even decoded.
+            if !self.peers.contains(&addr) {
+                continue;
+            }
+
+            if let Some(message) = Message::decode(&buf[..len]) {
+                self.apply(message);
+            }
+        }
+    }
+
+    fn apply(&self, message: Message) {
+        if message.node_id == self.node_id {
+            return;
+        }
+
+        let mut seen = self.seen.lock().unwrap();
+        if let Some((_, last_timestamp)) = seen.get(&message.key) {
+            if message.timestamp <= *last_timestamp {
+                return;
+            }
+        }
+        seen.insert(message.key.clone(), (message.node_id, message.timestamp));
+        drop(seen);
+
+        match message.payload {
+            Payload::Invalidate => {
+                self.cache.remove(&message.key);
+            }
+            Payload::Store { value, remaining_ttl } => {
+                if let Ok(value) = serde_json::from_slice::<T>(&value) {
+                    self.cache.insert_with_ttl(&message.key, value, remaining_ttl);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default, Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
+    struct DataTest {
+        value: String,
+    }
+
+    #[test]
+    fn store_message_round_trips_through_the_wire_format() {
+        let message = Message {
+            node_id: 7,
+            timestamp: 42,
+            key: "key".to_string(),
+            payload: Payload::Store {
+                value: b"value".to_vec(),
+                remaining_ttl: 1000,
+            },
+        };
+
+        let decoded = Message::decode(&message.encode()).expect("message should decode");
+
+        assert_eq!(decoded.node_id, 7);
+        assert_eq!(decoded.timestamp, 42);
+        assert_eq!(decoded.key, "key");
+        match decoded.payload {
+            Payload::Store { value, remaining_ttl } => {
+                assert_eq!(value, b"value");
+                assert_eq!(remaining_ttl, 1000);
+            }
+            Payload::Invalidate => panic!("expected a Store payload"),
+        }
+    }
+
+    #[test]
+    fn invalidate_message_round_trips_through_the_wire_format() {
+        let message = Message {
+            node_id: 1,
+            timestamp: 9,
+            key: "key".to_string(),
+            payload: Payload::Invalidate,
+        };
+
+        let decoded = Message::decode(&message.encode()).expect("message should decode");
+
+        assert_eq!(decoded.key, "key");
+        assert!(matches!(decoded.payload, Payload::Invalidate));
+    }
+}
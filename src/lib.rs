@@ -0,0 +1,4 @@
+pub mod cache;
+pub mod gossip;
+pub mod reaper;
+pub mod storage;
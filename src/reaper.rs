@@ -0,0 +1,156 @@
+use std::{fmt::Debug, sync::Arc, time::Duration};
+
+use tokio::sync::{mpsc, oneshot};
+
+use crate::cache::{Cache, CacheOption};
+
+pub enum Command<T> {
+    Get {
+        key: String,
+        reply: oneshot::Sender<CacheOption<T>>,
+    },
+    Update {
+        key: String,
+        value: T,
+        reply: oneshot::Sender<()>,
+    },
+    Remove {
+        key: String,
+        reply: oneshot::Sender<()>,
+    },
+    Clean {
+        key: String,
+        reply: oneshot::Sender<()>,
+    },
+}
+
+#[derive(Clone)]
+pub struct ReaperHandle<T> {
+    sender: mpsc::Sender<Command<T>>,
+}
+
+impl<T> ReaperHandle<T>
+where
+    T: Default + Debug + PartialEq + Clone + Send + 'static,
+{
+    pub async fn get(&self, key: String) -> CacheOption<T> {
+        let (reply, receiver) = oneshot::channel();
+        if self.sender.send(Command::Get { key, reply }).await.is_err() {
+            return CacheOption::Undefined;
+        }
+        receiver.await.unwrap_or(CacheOption::Undefined)
+    }
+
+    pub async fn update(&self, key: String, value: T) {
+        let (reply, receiver) = oneshot::channel();
+        if self
+            .sender
+            .send(Command::Update { key, value, reply })
+            .await
+            .is_ok()
+        {
+            receiver.await.ok();
+        }
+    }
+
+    pub async fn remove(&self, key: String) {
+        let (reply, receiver) = oneshot::channel();
+        if self.sender.send(Command::Remove { key, reply }).await.is_ok() {
+            receiver.await.ok();
+        }
+    }
+
+    pub async fn clean(&self, key: String) {
+        let (reply, receiver) = oneshot::channel();
+        if self.sender.send(Command::Clean { key, reply }).await.is_ok() {
+            receiver.await.ok();
+        }
+    }
+}
+
+/// Spawns a reaper actor owning `cache`. Handlers talk to the cache exclusively through
+/// the returned `ReaperHandle`, so many async callers can share one cache without each
+/// holding a lock across an `await`. A second task sweeps expired entries every
+/// `sweep_interval` so keys that are never read again don't leak forever.
+pub fn spawn<T>(cache: Cache<T>, sweep_interval: Duration) -> ReaperHandle<T>
+where
+    T: Default + Debug + PartialEq + Clone + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel(1024);
+    let cache = Arc::new(cache);
+
+    tokio::spawn(listen(cache.clone(), receiver));
+    tokio::spawn(reap(cache, sweep_interval));
+
+    ReaperHandle { sender }
+}
+
+async fn listen<T>(cache: Arc<Cache<T>>, mut receiver: mpsc::Receiver<Command<T>>)
+where
+    T: Default + Debug + PartialEq + Clone,
+{
+    while let Some(command) = receiver.recv().await {
+        match command {
+            Command::Get { key, reply } => {
+                reply.send(cache.get(&key)).ok();
+            }
+            Command::Update { key, value, reply } => {
+                cache.update(&key, value);
+                reply.send(()).ok();
+            }
+            Command::Remove { key, reply } => {
+                cache.remove(&key);
+                reply.send(()).ok();
+            }
+            Command::Clean { key, reply } => {
+                cache.clean(&key);
+                reply.send(()).ok();
+            }
+        }
+    }
+}
+
+async fn reap<T>(cache: Arc<Cache<T>>, sweep_interval: Duration)
+where
+    T: Default + Debug + PartialEq + Clone,
+{
+    let mut ticker = tokio::time::interval(sweep_interval);
+    loop {
+        ticker.tick().await;
+        cache.sweep_expired();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default, Debug, PartialEq, Clone)]
+    struct DataTest {
+        value: String,
+    }
+
+    #[tokio::test]
+    async fn update_then_get_round_trips_through_the_actor() {
+        let handle = spawn(Cache::<DataTest>::new(1000), Duration::from_secs(60));
+        let key = "key".to_string();
+        let value = DataTest {
+            value: "actor".to_string(),
+        };
+
+        handle.update(key.clone(), value.clone()).await;
+
+        assert_eq!(handle.get(key).await.unwrap(), value);
+    }
+
+    #[tokio::test]
+    async fn reaper_sweeps_expired_entries() {
+        let handle = spawn(Cache::<DataTest>::new(0), Duration::from_millis(10));
+        let key = "key".to_string();
+        handle.update(key.clone(), DataTest::default()).await;
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(handle.get(key).await.is_undefined(), true);
+    }
+}
@@ -0,0 +1,67 @@
+use async_trait::async_trait;
+use std::path::PathBuf;
+use tokio::fs;
+
+/// Durable backing store for a `Cache<T>`. Implementations persist raw, already
+/// serialized entries so cached values survive a process restart.
+#[async_trait]
+pub trait Storage {
+    async fn get(&self, key: &str) -> Option<Vec<u8>>;
+    async fn set(&self, key: &str, bytes: Vec<u8>);
+    async fn remove(&self, key: &str);
+}
+
+/// A `Storage` backed by a flat file per key in `base_dir`.
+pub struct FileStorage {
+    base_dir: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new(base_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            base_dir: base_dir.into(),
+        }
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        let file_name: String = key.as_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+        self.base_dir.join(file_name)
+    }
+}
+
+#[async_trait]
+impl Storage for FileStorage {
+    async fn get(&self, key: &str) -> Option<Vec<u8>> {
+        fs::read(self.path_for(key)).await.ok()
+    }
+
+    async fn set(&self, key: &str, bytes: Vec<u8>) {
+        if fs::create_dir_all(&self.base_dir).await.is_err() {
+            return;
+        }
+        fs::write(self.path_for(key), bytes).await.ok();
+    }
+
+    async fn remove(&self, key: &str) {
+        fs::remove_file(self.path_for(key)).await.ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn round_trips_bytes_through_a_file() {
+        let dir = std::env::temp_dir().join(format!("proxydis-storage-test-{:x}", std::process::id()));
+        let storage = FileStorage::new(dir.clone());
+
+        storage.set("key", b"value".to_vec()).await;
+        assert_eq!(storage.get("key").await, Some(b"value".to_vec()));
+
+        storage.remove("key").await;
+        assert_eq!(storage.get("key").await, None);
+
+        tokio::fs::remove_dir_all(dir).await.ok();
+    }
+}